@@ -0,0 +1,99 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::alloc::Layout;
+use std::fmt;
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use common_datavalues::prelude::*;
+use common_exception::Result;
+
+#[cfg(feature = "jit")]
+use crate::aggregates::jit::CompiledKernel;
+
+/// A raw pointer into the aggregate state area owned by the executor,
+/// offset to the slot this particular aggregate function reads/writes.
+#[derive(Clone, Copy)]
+pub struct StateAddr {
+    addr: usize,
+}
+
+impl StateAddr {
+    pub fn new(addr: usize) -> Self {
+        Self { addr }
+    }
+
+    pub fn next(&self, offset: usize) -> Self {
+        Self::new(self.addr + offset)
+    }
+
+    pub fn get<T>(&self) -> &mut T {
+        unsafe { &mut *(self.addr as *mut T) }
+    }
+
+    pub fn write<T, F>(&self, f: F)
+    where F: FnOnce() -> T {
+        unsafe {
+            let ptr = self.addr as *mut T;
+            std::ptr::write(ptr, f());
+        }
+    }
+}
+
+pub type AggregateFunctionRef = Arc<dyn AggregateFunction>;
+
+/// A single aggregate function instance, e.g. one `sum(x)` in a query.
+///
+/// Implementations hold compile-time-known argument/return types and are
+/// otherwise stateless; the actual accumulator state lives at the
+/// `StateAddr` the executor hands back into every call.
+pub trait AggregateFunction: fmt::Display + Sync + Send {
+    fn name(&self) -> &str;
+    fn return_type(&self) -> Result<DataType>;
+    fn nullable(&self, input_schema: &DataSchema) -> Result<bool>;
+
+    fn init_state(&self, place: StateAddr);
+    fn state_layout(&self) -> Layout;
+
+    fn accumulate(&self, place: StateAddr, arrays: &[Series], input_rows: usize) -> Result<()>;
+
+    fn accumulate_keys(
+        &self,
+        places: &[StateAddr],
+        offset: usize,
+        arrays: &[Series],
+        input_rows: usize,
+    ) -> Result<()>;
+
+    fn serialize(&self, place: StateAddr, writer: &mut BytesMut) -> Result<()>;
+    fn deserialize(&self, place: StateAddr, reader: &mut &[u8]) -> Result<()>;
+    fn merge(&self, place: StateAddr, rhs: StateAddr) -> Result<()>;
+    fn merge_result(&self, place: StateAddr) -> Result<DataValue>;
+
+    /// Opt-in native-code fast path for `accumulate`.
+    ///
+    /// When the `jit` feature is enabled and this returns `Some`, the
+    /// implementation's own `accumulate` invokes the compiled kernel
+    /// instead of its interpreted per-row loop. The kernel folds into a
+    /// single state, so `accumulate_keys` does not use it. The default
+    /// implementation declines, which keeps every aggregate on the
+    /// interpreted path until it opts in. Implementations are expected to
+    /// compile lazily and cache the result (e.g. behind a `OnceCell`), so
+    /// repeated calls don't recompile.
+    #[cfg(feature = "jit")]
+    fn jit_accumulate(&self) -> Option<Arc<CompiledKernel>> {
+        None
+    }
+}
@@ -0,0 +1,190 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::alloc::Layout;
+use std::fmt;
+use std::sync::Arc;
+
+use common_datavalues::prelude::*;
+use common_exception::ErrorCode;
+use common_exception::Result;
+use common_io::prelude::*;
+
+#[cfg(feature = "jit")]
+use once_cell::sync::OnceCell;
+
+use super::AggregateFunctionRef;
+use super::StateAddr;
+use crate::aggregates::aggregator_common::assert_unary_arguments;
+use crate::aggregates::AggregateFunction;
+#[cfg(feature = "jit")]
+use crate::aggregates::jit::compile_numeric_accumulate;
+#[cfg(feature = "jit")]
+use crate::aggregates::jit::CompiledKernel;
+#[cfg(feature = "jit")]
+use crate::aggregates::jit::NumericJitOp;
+
+/// `sum(x)` for a `UInt64` column.
+///
+/// The state is nothing but the running total at `StateAddr`, which is
+/// exactly the layout [`super::jit::compile_numeric_accumulate`] assumes
+/// for its `state_ptr` — this is the first aggregate wired up to the
+/// optional Cranelift fast path; `min`/`max` and the other numeric types
+/// can follow the same shape.
+#[derive(Clone)]
+pub struct AggregateSumU64Function {
+    display_name: String,
+    arguments: Vec<DataField>,
+    #[cfg(feature = "jit")]
+    jit_kernel: Arc<OnceCell<Option<Arc<CompiledKernel>>>>,
+}
+
+impl AggregateFunction for AggregateSumU64Function {
+    fn name(&self) -> &str {
+        "AggregateSumU64Function"
+    }
+
+    fn return_type(&self) -> Result<DataType> {
+        Ok(DataType::UInt64)
+    }
+
+    fn nullable(&self, _input_schema: &DataSchema) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn init_state(&self, place: StateAddr) {
+        place.write(|| 0u64);
+    }
+
+    fn state_layout(&self) -> Layout {
+        Layout::new::<u64>()
+    }
+
+    fn accumulate(&self, place: StateAddr, arrays: &[Series], input_rows: usize) -> Result<()> {
+        let array: &DataArray<UInt64Type> = arrays[0].static_cast();
+
+        #[cfg(feature = "jit")]
+        if let Some(kernel) = self.jit_accumulate() {
+            let values = array.downcast_ref().values();
+            let null_ptr = array
+                .downcast_ref()
+                .null_bitmap()
+                .map(|b| b.as_slice().as_ptr())
+                .unwrap_or(std::ptr::null());
+
+            // Safety: `values` backs `input_rows` contiguous `u64`s (plus
+            // an optional null bitmap with one bit per row), and `place`
+            // points at a single `u64`, matching the layout
+            // `compile_numeric_accumulate` was compiled against.
+            unsafe {
+                (kernel.func)(
+                    values.as_ptr() as *const u8,
+                    input_rows,
+                    null_ptr,
+                    place.get::<u64>() as *mut u64 as *mut u8,
+                );
+            }
+            return Ok(());
+        }
+
+        let state = place.get::<u64>();
+        for value in array.into_iter().flatten() {
+            *state += value;
+        }
+        Ok(())
+    }
+
+    fn accumulate_keys(
+        &self,
+        places: &[StateAddr],
+        offset: usize,
+        arrays: &[Series],
+        _input_rows: usize,
+    ) -> Result<()> {
+        let array: &DataArray<UInt64Type> = arrays[0].static_cast();
+        for (value, place) in array.into_iter().zip(places.iter()) {
+            if let Some(value) = value {
+                let state = place.next(offset).get::<u64>();
+                *state += value;
+            }
+        }
+        Ok(())
+    }
+
+    fn serialize(&self, place: StateAddr, writer: &mut BytesMut) -> Result<()> {
+        place.get::<u64>().serialize_to_buf(writer)
+    }
+
+    fn deserialize(&self, place: StateAddr, reader: &mut &[u8]) -> Result<()> {
+        *place.get::<u64>() = u64::deserialize(reader)?;
+        Ok(())
+    }
+
+    fn merge(&self, place: StateAddr, rhs: StateAddr) -> Result<()> {
+        *place.get::<u64>() += *rhs.get::<u64>();
+        Ok(())
+    }
+
+    fn merge_result(&self, place: StateAddr) -> Result<DataValue> {
+        Ok(DataValue::UInt64(Some(*place.get::<u64>())))
+    }
+
+    #[cfg(feature = "jit")]
+    fn jit_accumulate(&self) -> Option<Arc<CompiledKernel>> {
+        self.jit_kernel
+            .get_or_init(|| {
+                compile_numeric_accumulate(NumericJitOp::Sum, cranelift::prelude::types::I64)
+                    .ok()
+                    .map(Arc::new)
+            })
+            .clone()
+    }
+}
+
+impl fmt::Display for AggregateSumU64Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.display_name)
+    }
+}
+
+impl AggregateSumU64Function {
+    pub fn try_create(
+        display_name: &str,
+        arguments: Vec<DataField>,
+    ) -> Result<AggregateFunctionRef> {
+        Ok(Arc::new(Self {
+            display_name: display_name.to_owned(),
+            arguments,
+            #[cfg(feature = "jit")]
+            jit_kernel: Arc::new(OnceCell::new()),
+        }))
+    }
+}
+
+pub fn try_create_aggregate_sum_function(
+    display_name: &str,
+    arguments: Vec<DataField>,
+) -> Result<AggregateFunctionRef> {
+    assert_unary_arguments(display_name, arguments.len())?;
+
+    let data_type = arguments[0].data_type();
+    if data_type == DataType::UInt64 {
+        return AggregateSumU64Function::try_create(display_name, arguments);
+    }
+
+    Err(ErrorCode::BadDataValueType(format!(
+        "AggregateSumU64Function does not support type '{:?}'",
+        data_type
+    )))
+}
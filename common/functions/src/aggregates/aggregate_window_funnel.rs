@@ -30,13 +30,21 @@ use crate::aggregates::aggregator_common::assert_unary_arguments;
 use crate::aggregates::AggregateFunction;
 use crate::dispatch_unsigned_numeric_types;
 
+/// Sort-then-delta-encode-as-varint wire format (see
+/// [`AggregateWindowFunnelState::serialize`]). Chosen as `2` because the
+/// legacy fixed-width format's leading byte is always the `sorted` bool
+/// (`0` or `1`), so this tag can never collide with old data.
+const FORMAT_DELTA_VARINT: u8 = 2;
+
 struct AggregateWindowFunnelState<T> {
     pub events_list: Vec<(T, u8)>,
     pub sorted: bool,
 }
 
 impl<T> AggregateWindowFunnelState<T>
-where T: Ord + AsPrimitive<u64> + BinarySer + BinaryDe + Clone + Send + Sync + 'static
+where
+    T: Ord + AsPrimitive<u64> + BinarySer + BinaryDe + Clone + Copy + Send + Sync + 'static,
+    u64: AsPrimitive<T>,
 {
     fn new() -> Self {
         Self {
@@ -81,23 +89,20 @@ where T: Ord + AsPrimitive<u64> + BinarySer + BinaryDe + Clone + Send + Sync + '
         {
             let mut i = 0;
             let mut j = 0;
-            let mut k = 0;
             while i < l1 && j < l2 {
                 if cmp(&self.events_list[i], &other.events_list[j]) == Ordering::Less {
                     merged.push(self.events_list[i]);
-                    k += 1;
                     i += 1;
                 } else {
-                    merged.push(other.events_list[i]);
-                    k += 1;
+                    merged.push(other.events_list[j]);
                     j += 1;
                 }
             }
             if i < l1 {
-                merged[k..].copy_from_slice(&self.events_list[i..]);
+                merged.extend_from_slice(&self.events_list[i..]);
             }
             if j < l2 {
-                merged[k..].copy_from_slice(&other.events_list[i..]);
+                merged.extend_from_slice(&other.events_list[j..]);
             }
         }
         self.events_list = merged;
@@ -118,19 +123,77 @@ where T: Ord + AsPrimitive<u64> + BinarySer + BinaryDe + Clone + Send + Sync + '
         }
     }
 
+    /// Writes `events_list` sorted and delta-encoded: the first timestamp
+    /// in full, then every following timestamp as a uvarint delta from its
+    /// predecessor. Once sorted the deltas are non-negative and usually
+    /// tiny, which is far cheaper to shuffle/spill than a fixed-width
+    /// timestamp per event.
     fn serialize(&self, writer: &mut BytesMut) -> Result<()> {
-        self.sorted.serialize_to_buf(writer)?;
-        writer.write_uvarint(self.events_list.len() as u64)?;
+        FORMAT_DELTA_VARINT.serialize_to_buf(writer)?;
+
+        let mut events = self.events_list.clone();
+        let cmp = |a: &(T, u8), b: &(T, u8)| {
+            let ord = a.0.cmp(&b.0);
+            if ord == Ordering::Equal {
+                a.1.cmp(&b.1)
+            } else {
+                ord
+            }
+        };
+        if !self.sorted {
+            events.sort_by(cmp);
+        }
 
-        for (timestamp, event) in self.events_list.iter() {
-            timestamp.serialize_to_buf(writer)?;
-            event.serialize_to_buf(writer)?;
+        writer.write_uvarint(events.len() as u64)?;
+        if let Some((first_ts, first_event)) = events.first() {
+            first_ts.serialize_to_buf(writer)?;
+            first_event.serialize_to_buf(writer)?;
+
+            let mut prev: u64 = first_ts.as_();
+            for (timestamp, event) in events.iter().skip(1) {
+                let current: u64 = timestamp.as_();
+                writer.write_uvarint(current - prev)?;
+                event.serialize_to_buf(writer)?;
+                prev = current;
+            }
         }
         Ok(())
     }
 
     fn deserialize(&mut self, reader: &mut &[u8]) -> Result<()> {
-        self.sorted = bool::deserialize(reader)?;
+        let format = u8::deserialize(reader)?;
+        match format {
+            FORMAT_DELTA_VARINT => self.deserialize_delta_varint(reader),
+            // `0`/`1`: the legacy format's leading `sorted` bool, read inline.
+            _ => self.deserialize_fixed_width(format == 1, reader),
+        }
+    }
+
+    fn deserialize_delta_varint(&mut self, reader: &mut &[u8]) -> Result<()> {
+        let size: u64 = reader.read_uvarint()?;
+        self.events_list = Vec::with_capacity(size as usize);
+        self.sorted = true;
+
+        if size == 0 {
+            return Ok(());
+        }
+
+        let first_ts = T::deserialize(reader)?;
+        let first_event = u8::deserialize(reader)?;
+        self.events_list.push((first_ts, first_event));
+
+        let mut prev: u64 = first_ts.as_();
+        for _ in 1..size {
+            let delta: u64 = reader.read_uvarint()?;
+            let event = u8::deserialize(reader)?;
+            prev += delta;
+            self.events_list.push((prev.as_(), event));
+        }
+        Ok(())
+    }
+
+    fn deserialize_fixed_width(&mut self, sorted: bool, reader: &mut &[u8]) -> Result<()> {
+        self.sorted = sorted;
         let size: u64 = reader.read_uvarint()?;
         self.events_list = Vec::with_capacity(size as usize);
 
@@ -156,7 +219,8 @@ pub struct AggregateWindowFunnelFunction<T> {
 impl<T> AggregateFunction for AggregateWindowFunnelFunction<T>
 where
     T: DFNumericType,
-    T::Native: Ord + AsPrimitive<u64> + BinarySer + BinaryDe + Clone + Send + Sync + 'static,
+    T::Native: Ord + AsPrimitive<u64> + BinarySer + BinaryDe + Clone + Copy + Send + Sync + 'static,
+    u64: AsPrimitive<T::Native>,
 {
     fn name(&self) -> &str {
         "AggregateWindowFunnelFunction"
@@ -244,10 +308,8 @@ where
     }
 
     fn merge_result(&self, place: StateAddr) -> Result<DataValue> {
-        let state = place.get::<AggregateWindowFunnelState<T::Native>>();
-        state.sort();
-
-        todo!();
+        let level = self.get_event_level(place);
+        Ok(DataValue::UInt8(Some(level)))
     }
 }
 
@@ -260,27 +322,55 @@ impl<T> fmt::Display for AggregateWindowFunnelFunction<T> {
 impl<T> AggregateWindowFunnelFunction<T>
 where
     T: DFNumericType,
-    T::Native: Ord + AsPrimitive<u64> + BinarySer + BinaryDe + Clone + Send + Sync + 'static,
+    T::Native: Ord + AsPrimitive<u64> + BinarySer + BinaryDe + Clone + Copy + Send + Sync + 'static,
+    u64: AsPrimitive<T::Native>,
 {
     pub fn try_create(
         display_name: &str,
+        params: Vec<DataValue>,
         arguments: Vec<DataField>,
     ) -> Result<AggregateFunctionRef> {
         let event_size = arguments.len() - 1;
+        let window = Self::parse_window(display_name, &params)?;
         Ok(Arc::new(Self {
             display_name: display_name.to_owned(),
             arguments,
             event_size,
-            window: 1024,
+            window,
             t: PhantomData,
         }))
     }
 
+    fn parse_window(display_name: &str, params: &[DataValue]) -> Result<u64> {
+        let window = params.get(0).ok_or_else(|| {
+            ErrorCode::BadArguments(format!(
+                "{} expects a window size parameter, e.g. windowFunnel(window)(...)",
+                display_name
+            ))
+        })?;
+
+        let window = window.as_u64().map_err(|_| {
+            ErrorCode::BadArguments(format!(
+                "{} window size parameter must be a positive integer, got {:?}",
+                display_name, window
+            ))
+        })?;
+
+        if window == 0 {
+            return Err(ErrorCode::BadArguments(format!(
+                "{} window size parameter must be a positive integer, got 0",
+                display_name
+            )));
+        }
+
+        Ok(window)
+    }
+
     /// Loop through the entire events_list, update the event timestamp value
     /// The level path must be 1---2---3---...---check_events_size, find the max event level that satisfied the path in the sliding window.
     /// If found, returns the max event level, else return 0.
     /// The Algorithm complexity is O(n).
-    fn get_event_level(&mut self, place: StateAddr) -> u8 {
+    fn get_event_level(&self, place: StateAddr) -> u8 {
         let state = place.get::<AggregateWindowFunnelState<T::Native>>();
         if state.events_list.is_empty() {
             return 0;
@@ -291,45 +381,206 @@ where
 
         state.sort();
 
-        let mut events_timestamp: Vec<Option<u64>> = Vec::with_capacity(self.event_size);
-        for _i in 0..self.event_size {
-            events_timestamp.push(None);
-        }
-        let mut first_event = false;
+        let mut events_timestamp: Vec<Option<u64>> = vec![None; self.event_size];
 
         for (timestamp, event) in state.events_list.iter() {
-            let event_idx = event - 1;
-
-            if event_idx == 0 {
-                events_timestamp.push(Some(timestamp));
-                first_event = true;
-            } else if events_timestamp[event_idx - 1].is_some() {
+            let idx = (*event - 1) as usize;
+            let timestamp: u64 = timestamp.as_();
+
+            if idx == 0 {
+                events_timestamp[0] = Some(timestamp);
+            } else if let Some(start) = events_timestamp[idx - 1] {
+                if timestamp <= start + self.window {
+                    events_timestamp[idx] = Some(start);
+                    if idx + 1 == self.event_size {
+                        return self.event_size as u8;
+                    }
+                }
             }
         }
 
-        4
+        for (idx, ts) in events_timestamp.iter().enumerate().rev() {
+            if ts.is_some() {
+                return (idx + 1) as u8;
+            }
+        }
+        0
     }
 }
 
 macro_rules! creator {
-    ($T: ident, $data_type: expr, $display_name: expr, $arguments: expr) => {
+    ($T: ident, $data_type: expr, $display_name: expr, $params: expr, $arguments: expr) => {
         if $T::data_type() == $data_type {
-            return AggregateWindowFunnelFunction::<$T>::try_create($display_name, $arguments);
+            return AggregateWindowFunnelFunction::<$T>::try_create(
+                $display_name,
+                $params,
+                $arguments,
+            );
         }
     };
 }
 
 pub fn try_create_aggregate_WindowFunnel_function(
     display_name: &str,
+    params: Vec<DataValue>,
     arguments: Vec<DataField>,
 ) -> Result<AggregateFunctionRef> {
     assert_unary_arguments(display_name, arguments.len())?;
 
     let data_type = arguments[0].data_type();
-    dispatch_unsigned_numeric_types! {creator, data_type.clone(), display_name, arguments}
+    dispatch_unsigned_numeric_types! {creator, data_type.clone(), display_name, params, arguments}
 
     Err(ErrorCode::BadDataValueType(format!(
         "AggregateWindowFunnelFunction does not support type '{:?}'",
         data_type
     )))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_delta_varint_roundtrip_out_of_order() -> Result<()> {
+        let mut state = AggregateWindowFunnelState::<u32>::new();
+        // Fed out of order on purpose: serialize() must sort before encoding.
+        state.add(30, 2);
+        state.add(10, 1);
+        state.add(20, 1);
+
+        let mut buf = BytesMut::new();
+        state.serialize(&mut buf)?;
+
+        let mut reader: &[u8] = &buf;
+        let mut decoded = AggregateWindowFunnelState::<u32>::new();
+        decoded.deserialize(&mut reader)?;
+
+        assert!(decoded.sorted);
+        assert_eq!(decoded.events_list, vec![(10, 1), (20, 1), (30, 2)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_legacy_fixed_width_format() -> Result<()> {
+        let mut legacy = BytesMut::new();
+        true.serialize_to_buf(&mut legacy)?;
+        legacy.write_uvarint(2)?;
+        10u32.serialize_to_buf(&mut legacy)?;
+        1u8.serialize_to_buf(&mut legacy)?;
+        20u32.serialize_to_buf(&mut legacy)?;
+        1u8.serialize_to_buf(&mut legacy)?;
+
+        let mut reader: &[u8] = &legacy;
+        let mut decoded = AggregateWindowFunnelState::<u32>::new();
+        decoded.deserialize(&mut reader)?;
+
+        assert!(decoded.sorted);
+        assert_eq!(decoded.events_list, vec![(10, 1), (20, 1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_combines_and_sorts_events_from_both_states() {
+        // Regression test for the indexing bug this function used to have:
+        // `merge` pushed from the wrong side's array and then copied the
+        // tail with `copy_from_slice` into an already-full (i.e. empty)
+        // slice, which either dropped events or panicked.
+        let mut a = AggregateWindowFunnelState::<u32>::new();
+        a.add(30, 2);
+        a.add(10, 1);
+
+        let mut b = AggregateWindowFunnelState::<u32>::new();
+        b.add(20, 1);
+        b.add(40, 2);
+
+        a.merge(&mut b);
+
+        assert_eq!(a.events_list, vec![(10, 1), (20, 1), (30, 2), (40, 2)]);
+    }
+
+    #[test]
+    fn test_merge_with_unequal_length_states_copies_remaining_tail() {
+        let mut a = AggregateWindowFunnelState::<u32>::new();
+        a.add(10, 1);
+
+        let mut b = AggregateWindowFunnelState::<u32>::new();
+        b.add(20, 1);
+        b.add(30, 2);
+        b.add(40, 3);
+
+        a.merge(&mut b);
+
+        assert_eq!(a.events_list, vec![(10, 1), (20, 1), (30, 2), (40, 3)]);
+    }
+
+    fn place_for<T>() -> (StateAddr, Box<AggregateWindowFunnelState<T>>)
+    where
+        T: Ord + AsPrimitive<u64> + BinarySer + BinaryDe + Clone + Copy + Send + Sync + 'static,
+        u64: AsPrimitive<T>,
+    {
+        let mut boxed = Box::new(AggregateWindowFunnelState::<T>::new());
+        let place = StateAddr::new(boxed.as_mut() as *mut AggregateWindowFunnelState<T> as usize);
+        (place, boxed)
+    }
+
+    fn funnel_function(
+        event_size: usize,
+        window: u64,
+    ) -> AggregateWindowFunnelFunction<UInt32Type> {
+        AggregateWindowFunnelFunction::<UInt32Type> {
+            display_name: "windowFunnel".to_string(),
+            arguments: vec![],
+            event_size,
+            window,
+            t: PhantomData,
+        }
+    }
+
+    #[test]
+    fn test_get_event_level_full_chain_within_window() {
+        let func = funnel_function(3, 10);
+        let (place, _state) = place_for::<u32>();
+        let state = place.get::<AggregateWindowFunnelState<u32>>();
+        state.add(1, 1);
+        state.add(5, 2);
+        state.add(9, 3);
+
+        assert_eq!(func.get_event_level(place), 3);
+    }
+
+    #[test]
+    fn test_get_event_level_chain_broken_outside_window() {
+        let func = funnel_function(3, 2);
+        let (place, _state) = place_for::<u32>();
+        let state = place.get::<AggregateWindowFunnelState<u32>>();
+        state.add(1, 1);
+        // Outside the window of event 1 (1 + 2 = 3 < 5), so the chain
+        // can't continue past level 1.
+        state.add(5, 2);
+        state.add(6, 3);
+
+        assert_eq!(func.get_event_level(place), 1);
+    }
+
+    #[test]
+    fn test_get_event_level_keeps_latest_restart_of_first_event() {
+        let func = funnel_function(2, 10);
+        let (place, _state) = place_for::<u32>();
+        let state = place.get::<AggregateWindowFunnelState<u32>>();
+        // Two separate attempts at event 1; only the later one (at ts 50)
+        // is close enough to event 2 (at ts 55) to complete the funnel.
+        state.add(1, 1);
+        state.add(50, 1);
+        state.add(55, 2);
+
+        assert_eq!(func.get_event_level(place), 2);
+    }
+
+    #[test]
+    fn test_get_event_level_empty_state_is_zero() {
+        let func = funnel_function(2, 10);
+        let (place, _state) = place_for::<u32>();
+
+        assert_eq!(func.get_event_level(place), 0);
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use common_exception::ErrorCode;
+use common_exception::Result;
+use cranelift::prelude::*;
+use cranelift_jit::JITBuilder;
+use cranelift_jit::JITModule;
+use cranelift_module::Linkage;
+use cranelift_module::Module;
+
+use super::AccumulateFn;
+use super::CompiledKernel;
+
+/// The handful of reductions simple enough to be worth JIT-compiling: a
+/// straight-line loop with one comparison/add and no allocation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumericJitOp {
+    Sum,
+    Min,
+    Max,
+}
+
+/// Compiles `op` applied over a column of `ty` (one of Cranelift's native
+/// integer/float types) into a kernel with the [`super::AccumulateFn`]
+/// signature: `fn(col_ptr, len, null_bitmap_ptr, state_ptr)`.
+///
+/// `state_ptr` is assumed to point at a single value of `ty` that is
+/// read, folded with every non-null element, and written back — which is
+/// exactly the state layout `AggregateSumU64Function` uses.
+pub fn compile_numeric_accumulate(op: NumericJitOp, ty: types::Type) -> Result<CompiledKernel> {
+    let mut flag_builder = settings::builder();
+    flag_builder
+        .set("use_colocated_libcalls", "false")
+        .map_err(cranelift_error)?;
+    flag_builder.set("is_pic", "false").map_err(cranelift_error)?;
+    let isa_builder = cranelift_native::builder().map_err(|e| {
+        ErrorCode::UnImplement(format!("Cranelift is unavailable on this target: {}", e))
+    })?;
+    let isa = isa_builder
+        .finish(settings::Flags::new(flag_builder))
+        .map_err(cranelift_error)?;
+
+    let builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
+    let mut module = JITModule::new(builder);
+
+    let pointer_type = module.target_config().pointer_type;
+    let mut ctx = module.make_context();
+    ctx.func.signature.params.push(AbiParam::new(pointer_type)); // column ptr
+    ctx.func.signature.params.push(AbiParam::new(pointer_type)); // len
+    ctx.func.signature.params.push(AbiParam::new(pointer_type)); // null bitmap ptr
+    ctx.func.signature.params.push(AbiParam::new(pointer_type)); // state ptr
+
+    let mut fn_builder_ctx = FunctionBuilderContext::new();
+    let mut builder = FunctionBuilder::new(&mut ctx.func, &mut fn_builder_ctx);
+
+    let entry = builder.create_block();
+    builder.append_block_params_for_function_params(entry);
+    builder.switch_to_block(entry);
+    builder.seal_block(entry);
+
+    let col_ptr = builder.block_params(entry)[0];
+    let len = builder.block_params(entry)[1];
+    let null_ptr = builder.block_params(entry)[2];
+    let state_ptr = builder.block_params(entry)[3];
+
+    let loop_block = builder.create_block();
+    let body_block = builder.create_block();
+    let exit_block = builder.create_block();
+    builder.append_block_param(loop_block, ty); // accumulator
+    builder.append_block_param(loop_block, pointer_type); // index
+
+    let initial_acc = builder.ins().load(ty, MemFlags::trusted(), state_ptr, 0);
+    let zero_idx = builder.ins().iconst(pointer_type, 0);
+    builder.ins().jump(loop_block, &[initial_acc, zero_idx]);
+
+    builder.switch_to_block(loop_block);
+    let acc = builder.block_params(loop_block)[0];
+    let idx = builder.block_params(loop_block)[1];
+    let done = builder
+        .ins()
+        .icmp(IntCC::UnsignedGreaterThanOrEqual, idx, len);
+    builder.ins().brif(done, exit_block, &[], body_block, &[]);
+
+    builder.switch_to_block(body_block);
+    let is_null = load_null_bit(&mut builder, null_ptr, state_ptr, idx);
+    let elem_offset = builder.ins().imul_imm(idx, i64::from(ty.bytes()));
+    let elem_addr = builder.ins().iadd(col_ptr, elem_offset);
+    let value = builder.ins().load(ty, MemFlags::trusted(), elem_addr, 0);
+
+    let folded = match (op, ty.is_float()) {
+        (NumericJitOp::Sum, false) => builder.ins().iadd(acc, value),
+        (NumericJitOp::Sum, true) => builder.ins().fadd(acc, value),
+        (NumericJitOp::Min, false) => {
+            let cmp = builder.ins().icmp(IntCC::SignedLessThan, value, acc);
+            builder.ins().select(cmp, value, acc)
+        }
+        (NumericJitOp::Min, true) => builder.ins().fmin(acc, value),
+        (NumericJitOp::Max, false) => {
+            let cmp = builder.ins().icmp(IntCC::SignedGreaterThan, value, acc);
+            builder.ins().select(cmp, value, acc)
+        }
+        (NumericJitOp::Max, true) => builder.ins().fmax(acc, value),
+    };
+    let next_acc = builder.ins().select(is_null, acc, folded);
+    let next_idx = builder.ins().iadd_imm(idx, 1);
+    builder.ins().jump(loop_block, &[next_acc, next_idx]);
+
+    builder.switch_to_block(exit_block);
+    let final_acc = builder.block_params(loop_block)[0];
+    builder
+        .ins()
+        .store(MemFlags::trusted(), final_acc, state_ptr, 0);
+    builder.ins().return_(&[]);
+
+    builder.seal_block(loop_block);
+    builder.seal_block(body_block);
+    builder.seal_block(exit_block);
+    builder.finalize();
+
+    let name = format!("databend_jit_{:?}_{}", op, ty);
+    let func_id = module
+        .declare_function(&name, Linkage::Export, &ctx.func.signature)
+        .map_err(|e| ErrorCode::UnImplement(format!("Failed to declare JIT function: {}", e)))?;
+    module
+        .define_function(func_id, &mut ctx)
+        .map_err(|e| ErrorCode::UnImplement(format!("Failed to compile JIT function: {}", e)))?;
+    module.clear_context(&mut ctx);
+    module.finalize_definitions().map_err(cranelift_error)?;
+
+    let code = module.get_finalized_function(func_id);
+    // Safety: `code` was just compiled with exactly the `AccumulateFn`
+    // signature above, and `module` (kept alive in `CompiledKernel`)
+    // outlives every call through it.
+    let func: AccumulateFn = unsafe { std::mem::transmute(code) };
+
+    Ok(CompiledKernel::new(module, func))
+}
+
+/// `0` when the row at `idx` is non-null (or there is no null bitmap at
+/// all), `1` when it's null — matching the "skip this row" convention the
+/// `select` instructions above rely on.
+///
+/// Without a bitmap the byte is read from `fallback_ptr` (any readable
+/// address) instead of from the null pointer, and is then masked out.
+fn load_null_bit(
+    builder: &mut FunctionBuilder,
+    null_ptr: Value,
+    fallback_ptr: Value,
+    idx: Value,
+) -> Value {
+    let pointer_type = builder.func.signature.params[2].value_type;
+    let has_bitmap = builder.ins().icmp_imm(IntCC::NotEqual, null_ptr, 0);
+
+    let byte_idx = builder.ins().ushr_imm(idx, 3);
+    let bit_idx = builder.ins().band_imm(idx, 7);
+    let bitmap_addr = builder.ins().iadd(null_ptr, byte_idx);
+    let byte_addr = builder.ins().select(has_bitmap, bitmap_addr, fallback_ptr);
+    let byte = builder
+        .ins()
+        .load(types::I8, MemFlags::trusted(), byte_addr, 0);
+    let byte = builder.ins().uextend(pointer_type, byte);
+    let shifted = builder.ins().ushr(byte, bit_idx);
+    let bit = builder.ins().band_imm(shifted, 1);
+    let is_null_bit = builder.ins().icmp_imm(IntCC::Equal, bit, 0);
+
+    builder.ins().band(has_bitmap, is_null_bit)
+}
+
+fn cranelift_error<E: std::fmt::Display>(e: E) -> ErrorCode {
+    ErrorCode::UnImplement(format!("Cranelift JIT setup failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_sum_i64_skips_nulls() {
+        let kernel = compile_numeric_accumulate(NumericJitOp::Sum, types::I64).unwrap();
+
+        let values: [i64; 4] = [10, 20, 30, 40];
+        // Row 1 (value 20) is null and must not contribute to the sum.
+        let null_bitmap: [u8; 1] = [0b0000_1101];
+        let mut state: i64 = 5;
+
+        unsafe {
+            (kernel.func)(
+                values.as_ptr() as *const u8,
+                values.len(),
+                null_bitmap.as_ptr(),
+                &mut state as *mut i64 as *mut u8,
+            );
+        }
+
+        assert_eq!(state, 5 + 10 + 30 + 40);
+    }
+
+    #[test]
+    fn test_compile_max_f64_with_no_null_bitmap() {
+        let kernel = compile_numeric_accumulate(NumericJitOp::Max, types::F64).unwrap();
+
+        let values: [f64; 3] = [1.5, 9.25, 4.0];
+        let mut state: f64 = 0.0;
+
+        unsafe {
+            (kernel.func)(
+                values.as_ptr() as *const u8,
+                values.len(),
+                std::ptr::null(),
+                &mut state as *mut f64 as *mut u8,
+            );
+        }
+
+        assert_eq!(state, 9.25);
+    }
+}
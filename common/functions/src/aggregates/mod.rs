@@ -0,0 +1,27 @@
+// Copyright 2020 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod aggregate_function;
+mod aggregate_sum;
+mod aggregate_window_funnel;
+mod aggregator_common;
+
+#[cfg(feature = "jit")]
+mod jit;
+
+pub use aggregate_function::AggregateFunction;
+pub use aggregate_function::AggregateFunctionRef;
+pub use aggregate_function::StateAddr;
+pub use aggregate_sum::try_create_aggregate_sum_function;
+pub use aggregate_window_funnel::try_create_aggregate_WindowFunnel_function;
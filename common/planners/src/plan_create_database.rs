@@ -0,0 +1,28 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+/// The analyzed plan for `CREATE DATABASE ... [ENGINE = name] [WITH ...]`.
+///
+/// `engine` is the normalized (upper-cased) engine name returned by
+/// `validate_database_engine`; `options` holds whatever `WITH` key/value
+/// pairs that engine was given, already checked against its descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateDatabasePlan {
+    pub db: String,
+    pub engine: String,
+    pub options: HashMap<String, String>,
+    pub if_not_exists: bool,
+}
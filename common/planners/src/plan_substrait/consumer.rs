@@ -0,0 +1,526 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use common_datavalues::DataSchemaRef;
+use common_exception::ErrorCode;
+use common_exception::Result;
+use substrait::proto::expression::field_reference::ReferenceType as FieldReferenceType;
+use substrait::proto::expression::literal::LiteralType;
+use substrait::proto::expression::reference_segment::ReferenceType as SegmentReferenceType;
+use substrait::proto::expression::RexType;
+use substrait::proto::plan_rel::RelType as PlanRelType;
+use substrait::proto::read_rel::ReadType;
+use substrait::proto::rel::RelType;
+use substrait::proto::Expression as SubstraitExpression;
+use substrait::proto::Plan;
+use substrait::proto::Rel;
+
+use super::ddl::decode_create_database;
+use super::extensions::function_names_by_anchor;
+use super::table_resolver::SubstraitTableResolver;
+use crate::AggregatorFinalPlan;
+use crate::Expression;
+use crate::FilterPlan;
+use crate::LimitPlan;
+use crate::PlanNode;
+use crate::ProjectionPlan;
+use crate::SortExpr;
+use crate::SortPlan;
+
+/// Converts a Substrait `Plan` (as produced by another engine, or by
+/// [`super::to_substrait`]) back into a Databend `PlanNode` tree.
+///
+/// Like the producer, this only understands the relational shapes
+/// Databend's own planner can execute; anything else comes back as
+/// `ErrorCode::UnImplement` rather than a best-effort guess. Resolving a
+/// `NamedTable` read needs a catalog/session, which doesn't exist at this
+/// layer, so callers pass a [`SubstraitTableResolver`] rather than a
+/// concrete query-context type.
+pub fn from_substrait(plan: &Plan, resolver: &dyn SubstraitTableResolver) -> Result<PlanNode> {
+    let functions = function_names_by_anchor(&plan.extensions);
+
+    let root = plan
+        .relations
+        .get(0)
+        .and_then(|rel| rel.rel_type.as_ref())
+        .ok_or_else(|| ErrorCode::BadArguments("Substrait plan has no root relation"))?;
+
+    let rel = match root {
+        PlanRelType::Root(root) => root
+            .input
+            .as_ref()
+            .ok_or_else(|| ErrorCode::BadArguments("Substrait root relation has no input"))?,
+        PlanRelType::Rel(rel) => rel,
+    };
+
+    rel_to_plan(rel, &functions, resolver)
+}
+
+fn rel_to_plan(
+    rel: &Rel,
+    functions: &HashMap<u32, String>,
+    resolver: &dyn SubstraitTableResolver,
+) -> Result<PlanNode> {
+    match rel.rel_type.as_ref() {
+        Some(RelType::Read(read)) => read_to_plan(read, resolver),
+        Some(RelType::Filter(filter)) => {
+            let input = rel_to_plan(
+                filter
+                    .input
+                    .as_ref()
+                    .ok_or_else(|| ErrorCode::BadArguments("FilterRel has no input"))?,
+                functions,
+                resolver,
+            )?;
+            let predicate = filter
+                .condition
+                .as_ref()
+                .ok_or_else(|| ErrorCode::BadArguments("FilterRel has no condition"))?;
+            let schema = input.schema();
+
+            Ok(PlanNode::Filter(FilterPlan {
+                predicate: expression_from_substrait(predicate, &schema, functions)?,
+                input: Arc::new(input),
+            }))
+        }
+        Some(RelType::Project(project)) => {
+            let input = rel_to_plan(
+                project
+                    .input
+                    .as_ref()
+                    .ok_or_else(|| ErrorCode::BadArguments("ProjectRel has no input"))?,
+                functions,
+                resolver,
+            )?;
+            let schema = input.schema();
+            let expr = project
+                .expressions
+                .iter()
+                .map(|e| expression_from_substrait(e, &schema, functions))
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(PlanNode::Projection(ProjectionPlan {
+                expr,
+                schema: input.schema(),
+                input: Arc::new(input),
+            }))
+        }
+        Some(RelType::Aggregate(aggregate)) => {
+            let input = rel_to_plan(
+                aggregate
+                    .input
+                    .as_ref()
+                    .ok_or_else(|| ErrorCode::BadArguments("AggregateRel has no input"))?,
+                functions,
+                resolver,
+            )?;
+            let schema = input.schema();
+
+            let group_expr = aggregate
+                .groupings
+                .get(0)
+                .map(|grouping| {
+                    grouping
+                        .grouping_expressions
+                        .iter()
+                        .map(|e| expression_from_substrait(e, &schema, functions))
+                        .collect::<Result<Vec<_>>>()
+                })
+                .transpose()?
+                .unwrap_or_default();
+
+            let aggr_expr = aggregate
+                .measures
+                .iter()
+                .map(|measure| aggregate_function_from_measure(measure, &schema, functions))
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(PlanNode::AggregatorFinal(AggregatorFinalPlan {
+                group_expr,
+                aggr_expr,
+                schema: input.schema(),
+                input: Arc::new(input),
+            }))
+        }
+        Some(RelType::Sort(sort)) => {
+            let input = rel_to_plan(
+                sort.input
+                    .as_ref()
+                    .ok_or_else(|| ErrorCode::BadArguments("SortRel has no input"))?,
+                functions,
+                resolver,
+            )?;
+            let schema = input.schema();
+            let order_by = sort
+                .sorts
+                .iter()
+                .map(|s| sort_field_from_substrait(s, &schema, functions))
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(PlanNode::Sort(SortPlan {
+                order_by,
+                schema: input.schema(),
+                input: Arc::new(input),
+            }))
+        }
+        Some(RelType::Fetch(fetch)) => {
+            let input = rel_to_plan(
+                fetch
+                    .input
+                    .as_ref()
+                    .ok_or_else(|| ErrorCode::BadArguments("FetchRel has no input"))?,
+                functions,
+                resolver,
+            )?;
+
+            Ok(PlanNode::Limit(LimitPlan {
+                n: if fetch.count < 0 {
+                    None
+                } else {
+                    Some(fetch.count as usize)
+                },
+                offset: Some(fetch.offset as usize),
+                input: Arc::new(input),
+            }))
+        }
+        Some(RelType::ExtensionLeaf(leaf)) => {
+            let detail = leaf
+                .detail
+                .as_ref()
+                .ok_or_else(|| ErrorCode::BadArguments("ExtensionLeafRel has no detail"))?;
+            Ok(PlanNode::CreateDatabase(decode_create_database(detail)?))
+        }
+        other => Err(ErrorCode::UnImplement(format!(
+            "Substrait consumer does not support relation '{:?}' yet",
+            other
+        ))),
+    }
+}
+
+/// `AggregateRel` has no notion of the partial/final split Databend's own
+/// planner uses for distributed execution (see `super::producer::aggregate_to_rel`),
+/// so an ingested aggregate always comes back as a final aggregation; a
+/// later distributed-execution pass is free to split it into
+/// partial/final stages itself.
+fn aggregate_function_from_measure(
+    measure: &substrait::proto::aggregate_rel::Measure,
+    schema: &DataSchemaRef,
+    functions: &HashMap<u32, String>,
+) -> Result<Expression> {
+    let function = measure
+        .measure
+        .as_ref()
+        .ok_or_else(|| ErrorCode::BadArguments("AggregateRel measure has no function"))?;
+
+    let op = functions
+        .get(&function.function_reference)
+        .cloned()
+        .ok_or_else(|| {
+            ErrorCode::BadArguments(format!(
+                "Unregistered Substrait function anchor {}",
+                function.function_reference
+            ))
+        })?;
+
+    let args = function
+        .arguments
+        .iter()
+        .map(|arg| match &arg.arg_type {
+            Some(substrait::proto::function_argument::ArgType::Value(value)) => {
+                expression_from_substrait(value, schema, functions)
+            }
+            other => Err(ErrorCode::UnImplement(format!(
+                "Substrait consumer only supports value function arguments, got {:?}",
+                other
+            ))),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Expression::AggregateFunction {
+        op,
+        distinct: false,
+        params: vec![],
+        args,
+    })
+}
+
+fn read_to_plan(
+    read: &substrait::proto::ReadRel,
+    resolver: &dyn SubstraitTableResolver,
+) -> Result<PlanNode> {
+    let named_table = match &read.read_type {
+        Some(ReadType::NamedTable(table)) => table,
+        other => {
+            return Err(ErrorCode::UnImplement(format!(
+                "Substrait consumer only supports NamedTable reads, got '{:?}'",
+                other
+            )));
+        }
+    };
+
+    let (db, table) = match named_table.names.as_slice() {
+        [db, table] => (db.clone(), table.clone()),
+        [table] => (resolver.current_database(), table.clone()),
+        other => {
+            return Err(ErrorCode::BadArguments(format!(
+                "Substrait NamedTable expects [db, table] or [table], got {:?}",
+                other
+            )));
+        }
+    };
+
+    resolver.read_table_plan(&db, &table)
+}
+
+fn sort_field_from_substrait(
+    field: &substrait::proto::SortField,
+    schema: &DataSchemaRef,
+    functions: &HashMap<u32, String>,
+) -> Result<SortExpr> {
+    use substrait::proto::sort_field::SortDirection;
+    use substrait::proto::sort_field::SortKind;
+
+    let direction = match field.sort_kind.as_ref() {
+        Some(SortKind::Direction(d)) => SortDirection::from_i32(*d)
+            .ok_or_else(|| ErrorCode::BadArguments("Unknown Substrait sort direction"))?,
+        _ => {
+            return Err(ErrorCode::UnImplement(
+                "Substrait consumer only supports direction-based sort fields",
+            ));
+        }
+    };
+
+    let expr = expression_from_substrait(
+        field
+            .expr
+            .as_ref()
+            .ok_or_else(|| ErrorCode::BadArguments("SortField has no expression"))?,
+        schema,
+        functions,
+    )?;
+
+    let (asc, nulls_first) = match direction {
+        SortDirection::AscNullsFirst => (true, true),
+        SortDirection::AscNullsLast => (true, false),
+        SortDirection::DescNullsFirst => (false, true),
+        SortDirection::DescNullsLast => (false, false),
+        _ => {
+            return Err(ErrorCode::UnImplement(
+                "Substrait consumer does not support unspecified/clustered sort direction",
+            ));
+        }
+    };
+
+    Ok(SortExpr {
+        expr,
+        asc,
+        nulls_first,
+    })
+}
+
+fn expression_from_substrait(
+    expr: &SubstraitExpression,
+    schema: &DataSchemaRef,
+    functions: &HashMap<u32, String>,
+) -> Result<Expression> {
+    match expr.rex_type.as_ref() {
+        Some(RexType::Selection(selection)) => {
+            let name = column_name_from_selection(selection, schema)?;
+            Ok(Expression::Column(name))
+        }
+        Some(RexType::Literal(literal)) => literal_from_substrait(literal),
+        Some(RexType::ScalarFunction(func)) => {
+            let op = functions
+                .get(&func.function_reference)
+                .cloned()
+                .ok_or_else(|| {
+                    ErrorCode::BadArguments(format!(
+                        "Unregistered Substrait function anchor {}",
+                        func.function_reference
+                    ))
+                })?;
+            let args = func
+                .arguments
+                .iter()
+                .map(|arg| match &arg.arg_type {
+                    Some(substrait::proto::function_argument::ArgType::Value(value)) => {
+                        expression_from_substrait(value, schema, functions)
+                    }
+                    other => Err(ErrorCode::UnImplement(format!(
+                        "Substrait consumer only supports value function arguments, got {:?}",
+                        other
+                    ))),
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(Expression::ScalarFunction { op, args })
+        }
+        other => Err(ErrorCode::UnImplement(format!(
+            "Substrait consumer does not support expression '{:?}' yet",
+            other
+        ))),
+    }
+}
+
+/// Resolves a `FieldReference` back to a column name by its positional
+/// `field` index against `schema` — the index is the only part of the
+/// reference a foreign producer (e.g. DataFusion) is guaranteed to set
+/// correctly; `expr_name` is Databend's own round-tripping convenience and
+/// isn't trusted on its own.
+fn column_name_from_selection(
+    selection: &substrait::proto::expression::FieldReference,
+    schema: &DataSchemaRef,
+) -> Result<String> {
+    let direct = match selection.reference_type.as_ref() {
+        Some(FieldReferenceType::DirectReference(segment)) => segment,
+        other => {
+            return Err(ErrorCode::UnImplement(format!(
+                "Substrait consumer only supports direct field references, got {:?}",
+                other
+            )));
+        }
+    };
+
+    let field = match direct.reference_type.as_ref() {
+        Some(SegmentReferenceType::StructField(struct_field)) => struct_field.field,
+        other => {
+            return Err(ErrorCode::UnImplement(format!(
+                "Substrait consumer only supports struct field references, got {:?}",
+                other
+            )));
+        }
+    };
+
+    let field = schema.field(field as usize).map_err(|_| {
+        ErrorCode::BadArguments(format!(
+            "Substrait consumer could not resolve field index {} against the input schema",
+            field
+        ))
+    })?;
+
+    Ok(field.name().clone())
+}
+
+fn literal_from_substrait(literal: &substrait::proto::expression::Literal) -> Result<Expression> {
+    use common_datavalues::DataValue;
+
+    let value = match &literal.literal_type {
+        Some(LiteralType::Boolean(v)) => DataValue::Boolean(Some(*v)),
+        Some(LiteralType::I64(v)) => DataValue::Int64(Some(*v)),
+        Some(LiteralType::Fp64(v)) => DataValue::Float64(Some(*v)),
+        Some(LiteralType::String(v)) => DataValue::String(Some(v.clone().into_bytes())),
+        other => {
+            return Err(ErrorCode::UnImplement(format!(
+                "Substrait consumer does not support literal '{:?}' yet",
+                other
+            )));
+        }
+    };
+
+    Ok(Expression::Literal(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use common_datavalues::DataField;
+    use common_datavalues::DataSchemaRefExt;
+    use common_datavalues::DataType;
+    use substrait::proto::expression::field_reference::ReferenceType as FieldReferenceType;
+    use substrait::proto::expression::reference_segment;
+    use substrait::proto::expression::reference_segment::StructField;
+    use substrait::proto::expression::FieldReference;
+    use substrait::proto::expression::ReferenceSegment;
+
+    use super::*;
+
+    #[test]
+    fn test_column_name_resolves_by_field_index_not_expr_name() {
+        let schema = DataSchemaRefExt::create(vec![
+            DataField::new("a", DataType::Int64, false),
+            DataField::new("b", DataType::Int64, false),
+        ]);
+
+        // A plan from another engine sets `field` correctly but leaves
+        // `expr_name` empty; the consumer must still resolve column "b".
+        let selection = FieldReference {
+            reference_type: Some(FieldReferenceType::DirectReference(ReferenceSegment {
+                reference_type: Some(reference_segment::ReferenceType::StructField(Box::new(
+                    StructField {
+                        field: 1,
+                        child: None,
+                    },
+                ))),
+            })),
+            root_type: None,
+            expr_name: String::new(),
+        };
+
+        let name = column_name_from_selection(&selection, &schema).unwrap();
+        assert_eq!(name, "b");
+    }
+
+    #[test]
+    fn test_aggregate_function_from_measure_resolves_anchor_and_args() {
+        use substrait::proto::aggregate_rel::Measure;
+        use substrait::proto::function_argument::ArgType;
+        use substrait::proto::AggregateFunction;
+        use substrait::proto::FunctionArgument;
+
+        let schema = DataSchemaRefExt::create(vec![DataField::new("a", DataType::Int64, false)]);
+        let mut functions = HashMap::new();
+        functions.insert(7, "sum".to_string());
+
+        let selection = FieldReference {
+            reference_type: Some(FieldReferenceType::DirectReference(ReferenceSegment {
+                reference_type: Some(reference_segment::ReferenceType::StructField(Box::new(
+                    StructField {
+                        field: 0,
+                        child: None,
+                    },
+                ))),
+            })),
+            root_type: None,
+            expr_name: String::new(),
+        };
+
+        let measure = Measure {
+            measure: Some(AggregateFunction {
+                function_reference: 7,
+                arguments: vec![FunctionArgument {
+                    arg_type: Some(ArgType::Value(SubstraitExpression {
+                        rex_type: Some(RexType::Selection(Box::new(selection))),
+                    })),
+                }],
+                ..Default::default()
+            }),
+            filter: None,
+        };
+
+        let expr = aggregate_function_from_measure(&measure, &schema, &functions).unwrap();
+        match expr {
+            Expression::AggregateFunction { op, args, .. } => {
+                assert_eq!(op, "sum");
+                assert_eq!(args.len(), 1);
+                match &args[0] {
+                    Expression::Column(name) => assert_eq!(name, "a"),
+                    other => panic!("expected a column expression, got {:?}", other),
+                }
+            }
+            other => panic!("expected an aggregate function expression, got {:?}", other),
+        }
+    }
+}
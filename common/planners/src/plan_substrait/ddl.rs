@@ -0,0 +1,150 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use common_exception::ErrorCode;
+use common_exception::Result;
+
+use crate::CreateDatabasePlan;
+
+/// Substrait's relational algebra has no DDL rels, so `CreateDatabasePlan`
+/// round-trips the same way any other vendor-specific relation would:
+/// as an opaque `google.protobuf.Any` carried by an `ExtensionLeafRel`,
+/// tagged with a Databend-owned type URL both ends agree on.
+const CREATE_DATABASE_TYPE_URL: &str = "type.databend.io/databend.plan.CreateDatabasePlan";
+
+pub fn encode_create_database(plan: &CreateDatabasePlan) -> ::prost_types::Any {
+    let mut buf = Vec::new();
+    write_string(&mut buf, &plan.db);
+    write_string(&mut buf, &plan.engine);
+    buf.push(plan.if_not_exists as u8);
+    write_u32(&mut buf, plan.options.len() as u32);
+    for (key, value) in &plan.options {
+        write_string(&mut buf, key);
+        write_string(&mut buf, value);
+    }
+
+    ::prost_types::Any {
+        type_url: CREATE_DATABASE_TYPE_URL.to_string(),
+        value: buf,
+    }
+}
+
+pub fn decode_create_database(any: &::prost_types::Any) -> Result<CreateDatabasePlan> {
+    if any.type_url != CREATE_DATABASE_TYPE_URL {
+        return Err(ErrorCode::BadArguments(format!(
+            "Substrait consumer expected a CreateDatabasePlan extension, got type url '{}'",
+            any.type_url
+        )));
+    }
+
+    let mut cursor = any.value.as_slice();
+    let db = read_string(&mut cursor)?;
+    let engine = read_string(&mut cursor)?;
+    let if_not_exists = read_byte(&mut cursor)? != 0;
+    let option_count = read_u32(&mut cursor)?;
+
+    let mut options = HashMap::with_capacity(option_count as usize);
+    for _ in 0..option_count {
+        let key = read_string(&mut cursor)?;
+        let value = read_string(&mut cursor)?;
+        options.insert(key, value);
+    }
+
+    Ok(CreateDatabasePlan {
+        db,
+        engine,
+        options,
+        if_not_exists,
+    })
+}
+
+fn write_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+fn write_string(buf: &mut Vec<u8>, value: &str) {
+    write_u32(buf, value.len() as u32);
+    buf.extend_from_slice(value.as_bytes());
+}
+
+fn read_u32(cursor: &mut &[u8]) -> Result<u32> {
+    if cursor.len() < 4 {
+        return Err(truncated());
+    }
+    let (bytes, rest) = cursor.split_at(4);
+    *cursor = rest;
+    Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_byte(cursor: &mut &[u8]) -> Result<u8> {
+    if cursor.is_empty() {
+        return Err(truncated());
+    }
+    let (byte, rest) = cursor.split_at(1);
+    *cursor = rest;
+    Ok(byte[0])
+}
+
+fn read_string(cursor: &mut &[u8]) -> Result<String> {
+    let len = read_u32(cursor)? as usize;
+    if cursor.len() < len {
+        return Err(truncated());
+    }
+    let (bytes, rest) = cursor.split_at(len);
+    *cursor = rest;
+    String::from_utf8(bytes.to_vec()).map_err(|_| {
+        ErrorCode::BadArguments(
+            "Substrait consumer read invalid UTF-8 in a CreateDatabasePlan extension",
+        )
+    })
+}
+
+fn truncated() -> ErrorCode {
+    ErrorCode::BadArguments("Substrait consumer read a truncated CreateDatabasePlan extension")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_database_roundtrip() {
+        let mut options = HashMap::new();
+        options.insert("url".to_string(), "http://example.com".to_string());
+
+        let plan = CreateDatabasePlan {
+            db: "db1".to_string(),
+            engine: "DEFAULT".to_string(),
+            options,
+            if_not_exists: true,
+        };
+
+        let any = encode_create_database(&plan);
+        let decoded = decode_create_database(&any).unwrap();
+        assert_eq!(decoded, plan);
+    }
+
+    #[test]
+    fn test_decode_rejects_wrong_type_url() {
+        let any = ::prost_types::Any {
+            type_url: "type.databend.io/not.the.right.type".to_string(),
+            value: vec![],
+        };
+        let err = decode_create_database(&any).unwrap_err();
+        assert!(err.message().contains("expected a CreateDatabasePlan"));
+    }
+}
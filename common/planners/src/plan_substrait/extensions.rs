@@ -0,0 +1,122 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use substrait::proto::extensions::simple_extension_declaration::ExtensionFunction;
+use substrait::proto::extensions::simple_extension_declaration::MappingType;
+use substrait::proto::extensions::SimpleExtensionDeclaration;
+use substrait::proto::extensions::SimpleExtensionUri;
+
+/// Databend's own extension namespace, used for scalar/aggregate functions
+/// that don't have a well-known Substrait YAML URI yet.
+const DATABEND_EXTENSION_URI: &str =
+    "https://github.com/datafuselabs/databend/blob/main/substrait/extensions.yaml";
+
+/// Assigns stable anchors to function names the first time they're seen
+/// during a `to_substrait` conversion, and accumulates the
+/// `SimpleExtensionUri`/`SimpleExtensionDeclaration` lists that must be
+/// embedded in the final `Plan`.
+#[derive(Default)]
+pub struct FunctionRegistry {
+    uri_anchor: Option<u32>,
+    anchors: HashMap<String, u32>,
+    declarations: Vec<SimpleExtensionDeclaration>,
+}
+
+impl FunctionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the (possibly newly assigned) function anchor for `name`,
+    /// registering it in the extension declarations on first use.
+    pub fn anchor(&mut self, name: &str) -> u32 {
+        if let Some(anchor) = self.anchors.get(name) {
+            return *anchor;
+        }
+
+        let anchor = self.anchors.len() as u32;
+        self.anchors.insert(name.to_string(), anchor);
+        self.declarations.push(SimpleExtensionDeclaration {
+            mapping_type: Some(MappingType::ExtensionFunction(ExtensionFunction {
+                extension_uri_reference: self.uri_anchor(),
+                function_anchor: anchor,
+                name: name.to_string(),
+            })),
+        });
+        anchor
+    }
+
+    fn uri_anchor(&mut self) -> u32 {
+        *self.uri_anchor.get_or_insert(0)
+    }
+
+    pub fn extension_uris(&self) -> Vec<SimpleExtensionUri> {
+        if self.declarations.is_empty() {
+            return vec![];
+        }
+        vec![SimpleExtensionUri {
+            extension_uri_anchor: 0,
+            uri: DATABEND_EXTENSION_URI.to_string(),
+        }]
+    }
+
+    pub fn into_declarations(self) -> Vec<SimpleExtensionDeclaration> {
+        self.declarations
+    }
+}
+
+/// Resolves a function anchor back to its name while reading a plan,
+/// the mirror image of `FunctionRegistry`.
+pub fn function_names_by_anchor(
+    declarations: &[SimpleExtensionDeclaration],
+) -> HashMap<u32, String> {
+    let mut names = HashMap::new();
+    for declaration in declarations {
+        if let Some(MappingType::ExtensionFunction(f)) = &declaration.mapping_type {
+            names.insert(f.function_anchor, f.name.clone());
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anchor_is_stable_and_reused() {
+        let mut registry = FunctionRegistry::new();
+
+        let plus_anchor = registry.anchor("+");
+        let minus_anchor = registry.anchor("-");
+        assert_eq!(registry.anchor("+"), plus_anchor);
+        assert_ne!(plus_anchor, minus_anchor);
+
+        let declarations = registry.into_declarations();
+        assert_eq!(declarations.len(), 2);
+    }
+
+    #[test]
+    fn test_function_names_by_anchor_round_trips_registry() {
+        let mut registry = FunctionRegistry::new();
+        let plus_anchor = registry.anchor("+");
+        let minus_anchor = registry.anchor("-");
+
+        let names = function_names_by_anchor(&registry.into_declarations());
+        assert_eq!(names.get(&plus_anchor).map(String::as_str), Some("+"));
+        assert_eq!(names.get(&minus_anchor).map(String::as_str), Some("-"));
+    }
+}
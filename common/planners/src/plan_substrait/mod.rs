@@ -0,0 +1,28 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion between Databend's `PlanNode` tree and the Substrait
+//! cross-engine plan protobuf, so plans produced by other engines can be
+//! ingested and Databend's own plans can be handed to external optimizers
+//! without either side hand-rolling a bespoke serialization.
+
+mod consumer;
+mod ddl;
+mod extensions;
+mod producer;
+mod table_resolver;
+
+pub use consumer::from_substrait;
+pub use producer::to_substrait;
+pub use table_resolver::SubstraitTableResolver;
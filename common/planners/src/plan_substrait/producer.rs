@@ -0,0 +1,456 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use common_datavalues::DataSchemaRef;
+use common_exception::ErrorCode;
+use common_exception::Result;
+use substrait::proto::expression::field_reference::ReferenceType as FieldReferenceType;
+use substrait::proto::expression::literal::LiteralType;
+use substrait::proto::expression::reference_segment;
+use substrait::proto::expression::reference_segment::StructField;
+use substrait::proto::expression::Literal;
+use substrait::proto::expression::ReferenceSegment;
+use substrait::proto::expression::RexType;
+use substrait::proto::function_argument::ArgType;
+use substrait::proto::plan_rel::RelType as PlanRelType;
+use substrait::proto::read_rel::NamedTable;
+use substrait::proto::read_rel::ReadType;
+use substrait::proto::rel::RelType;
+use substrait::proto::AggregateRel;
+use substrait::proto::Expression;
+use substrait::proto::ExtensionLeafRel;
+use substrait::proto::FetchRel;
+use substrait::proto::FilterRel;
+use substrait::proto::FunctionArgument;
+use substrait::proto::Plan;
+use substrait::proto::PlanRel;
+use substrait::proto::ProjectRel;
+use substrait::proto::ReadRel;
+use substrait::proto::Rel;
+use substrait::proto::RelRoot;
+use substrait::proto::ScalarFunction;
+use substrait::proto::SortField;
+use substrait::proto::SortRel;
+
+use super::ddl::encode_create_database;
+use super::extensions::FunctionRegistry;
+use crate::AggregatorFinalPlan;
+use crate::AggregatorPartialPlan;
+use crate::CreateDatabasePlan;
+use crate::Expression as DfExpression;
+use crate::FilterPlan;
+use crate::LimitPlan;
+use crate::PlanNode;
+use crate::ProjectionPlan;
+use crate::ReadDataSourcePlan;
+use crate::SortPlan;
+
+/// Converts a Databend `PlanNode` tree into a Substrait `Plan`.
+///
+/// Only the operators Databend's planner actually produces are lowered:
+/// scan, filter, projection, partial/final aggregation, sort and limit,
+/// plus `CreateDatabasePlan`, which travels as an opaque extension rel.
+/// Anything else (other DDL, explain, and operators the planner hasn't
+/// grown yet, e.g. an explicit join node) is rejected with
+/// `ErrorCode::UnImplement` rather than silently dropped.
+pub fn to_substrait(plan: &PlanNode) -> Result<Plan> {
+    let mut registry = FunctionRegistry::new();
+    let rel = plan_to_rel(plan, &mut registry)?;
+    let names = output_names(plan);
+
+    Ok(Plan {
+        extension_uris: registry.extension_uris(),
+        extensions: registry.into_declarations(),
+        relations: vec![PlanRel {
+            rel_type: Some(PlanRelType::Root(RelRoot {
+                input: Some(rel),
+                names,
+            })),
+        }],
+        ..Default::default()
+    })
+}
+
+fn plan_to_rel(plan: &PlanNode, registry: &mut FunctionRegistry) -> Result<Rel> {
+    match plan {
+        PlanNode::ReadSource(plan) => read_source_to_rel(plan),
+        PlanNode::Filter(plan) => filter_to_rel(plan, registry),
+        PlanNode::Projection(plan) => projection_to_rel(plan, registry),
+        PlanNode::AggregatorPartial(plan) => aggregator_partial_to_rel(plan, registry),
+        PlanNode::AggregatorFinal(plan) => aggregator_final_to_rel(plan, registry),
+        PlanNode::Sort(plan) => sort_to_rel(plan, registry),
+        PlanNode::Limit(plan) => limit_to_rel(plan, registry),
+        PlanNode::CreateDatabase(plan) => create_database_to_rel(plan),
+        other => Err(ErrorCode::UnImplement(format!(
+            "Substrait producer does not support plan node '{}' yet",
+            other.name()
+        ))),
+    }
+}
+
+/// Substrait has no DDL relation, so `CreateDatabasePlan` is carried as an
+/// opaque extension rel other Databend producers/consumers can read back;
+/// an external engine that doesn't understand the Databend type URL
+/// inside it will (correctly) fail to make sense of it, same as any other
+/// vendor extension.
+fn create_database_to_rel(plan: &CreateDatabasePlan) -> Result<Rel> {
+    Ok(Rel {
+        rel_type: Some(RelType::ExtensionLeaf(ExtensionLeafRel {
+            common: None,
+            detail: Some(encode_create_database(plan)),
+        })),
+    })
+}
+
+fn input_rel(input: &PlanNode, registry: &mut FunctionRegistry) -> Result<Box<Rel>> {
+    Ok(Box::new(plan_to_rel(input, registry)?))
+}
+
+fn read_source_to_rel(plan: &ReadDataSourcePlan) -> Result<Rel> {
+    Ok(Rel {
+        rel_type: Some(RelType::Read(Box::new(ReadRel {
+            base_schema: None,
+            read_type: Some(ReadType::NamedTable(NamedTable {
+                names: vec![plan.table_info.db.clone(), plan.table_info.name.clone()],
+                advanced_extension: None,
+            })),
+            ..Default::default()
+        }))),
+    })
+}
+
+fn filter_to_rel(plan: &FilterPlan, registry: &mut FunctionRegistry) -> Result<Rel> {
+    let schema = plan.input.schema();
+    let input = input_rel(&plan.input, registry)?;
+    let condition = expression_to_substrait(&plan.predicate, &schema, registry)?;
+
+    Ok(Rel {
+        rel_type: Some(RelType::Filter(Box::new(FilterRel {
+            input: Some(input),
+            condition: Some(Box::new(condition)),
+            ..Default::default()
+        }))),
+    })
+}
+
+fn projection_to_rel(plan: &ProjectionPlan, registry: &mut FunctionRegistry) -> Result<Rel> {
+    let schema = plan.input.schema();
+    let input = input_rel(&plan.input, registry)?;
+    let expressions = plan
+        .expr
+        .iter()
+        .map(|expr| expression_to_substrait(expr, &schema, registry))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Rel {
+        rel_type: Some(RelType::Project(Box::new(ProjectRel {
+            input: Some(input),
+            expressions,
+            ..Default::default()
+        }))),
+    })
+}
+
+fn aggregator_partial_to_rel(
+    plan: &AggregatorPartialPlan,
+    registry: &mut FunctionRegistry,
+) -> Result<Rel> {
+    aggregate_to_rel(&plan.input, &plan.group_expr, &plan.aggr_expr, registry)
+}
+
+fn aggregator_final_to_rel(
+    plan: &AggregatorFinalPlan,
+    registry: &mut FunctionRegistry,
+) -> Result<Rel> {
+    aggregate_to_rel(&plan.input, &plan.group_expr, &plan.aggr_expr, registry)
+}
+
+/// Substrait has no notion of a partial/final aggregate split (that's a
+/// distributed-execution detail), so both Databend aggregate plan nodes
+/// lower to the same `AggregateRel` shape.
+fn aggregate_to_rel(
+    input: &PlanNode,
+    group_expr: &[DfExpression],
+    aggr_expr: &[DfExpression],
+    registry: &mut FunctionRegistry,
+) -> Result<Rel> {
+    use substrait::proto::aggregate_function::AggregationInvocation;
+    use substrait::proto::aggregate_rel::Grouping;
+    use substrait::proto::aggregate_rel::Measure;
+    use substrait::proto::AggregateFunction;
+
+    let schema = input.schema();
+    let input = input_rel(input, registry)?;
+
+    let groupings = vec![Grouping {
+        grouping_expressions: group_expr
+            .iter()
+            .map(|expr| expression_to_substrait(expr, &schema, registry))
+            .collect::<Result<Vec<_>>>()?,
+    }];
+
+    let measures = aggr_expr
+        .iter()
+        .map(|expr| {
+            let (name, args) = match expr {
+                DfExpression::AggregateFunction { op, args, .. } => (op.clone(), args.clone()),
+                other => {
+                    return Err(ErrorCode::UnImplement(format!(
+                        "Expected an aggregate function expression, got {:?}",
+                        other
+                    )));
+                }
+            };
+            let anchor = registry.anchor(&name);
+            let arguments = args
+                .iter()
+                .map(|arg| {
+                    Ok(FunctionArgument {
+                        arg_type: Some(ArgType::Value(expression_to_substrait(
+                            arg, &schema, registry,
+                        )?)),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(Measure {
+                measure: Some(AggregateFunction {
+                    function_reference: anchor,
+                    arguments,
+                    invocation: AggregationInvocation::All as i32,
+                    ..Default::default()
+                }),
+                filter: None,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Rel {
+        rel_type: Some(RelType::Aggregate(Box::new(AggregateRel {
+            input: Some(input),
+            groupings,
+            measures,
+            ..Default::default()
+        }))),
+    })
+}
+
+fn sort_to_rel(plan: &SortPlan, registry: &mut FunctionRegistry) -> Result<Rel> {
+    use substrait::proto::sort_field::SortDirection;
+
+    let schema = plan.input.schema();
+    let input = input_rel(&plan.input, registry)?;
+    let sorts = plan
+        .order_by
+        .iter()
+        .map(|sort| {
+            let direction = match (sort.asc, sort.nulls_first) {
+                (true, true) => SortDirection::AscNullsFirst,
+                (true, false) => SortDirection::AscNullsLast,
+                (false, true) => SortDirection::DescNullsFirst,
+                (false, false) => SortDirection::DescNullsLast,
+            };
+            Ok(SortField {
+                expr: Some(expression_to_substrait(&sort.expr, &schema, registry)?),
+                sort_kind: Some(substrait::proto::sort_field::SortKind::Direction(
+                    direction as i32,
+                )),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Rel {
+        rel_type: Some(RelType::Sort(Box::new(SortRel {
+            input: Some(input),
+            sorts,
+            ..Default::default()
+        }))),
+    })
+}
+
+fn limit_to_rel(plan: &LimitPlan, registry: &mut FunctionRegistry) -> Result<Rel> {
+    let input = input_rel(&plan.input, registry)?;
+
+    Ok(Rel {
+        rel_type: Some(RelType::Fetch(Box::new(FetchRel {
+            input: Some(input),
+            offset: plan.offset.unwrap_or(0) as i64,
+            count: plan.n.map(|n| n as i64).unwrap_or(-1),
+            ..Default::default()
+        }))),
+    })
+}
+
+fn expression_to_substrait(
+    expr: &DfExpression,
+    schema: &DataSchemaRef,
+    registry: &mut FunctionRegistry,
+) -> Result<Expression> {
+    let rex_type = match expr {
+        DfExpression::Column(name) => {
+            let index = schema.index_of(name).map_err(|_| {
+                ErrorCode::BadArguments(format!(
+                    "Substrait producer could not resolve column '{}' against the input schema",
+                    name
+                ))
+            })?;
+            RexType::Selection(Box::new(substrait::proto::expression::FieldReference {
+                reference_type: Some(FieldReferenceType::DirectReference(ReferenceSegment {
+                    reference_type: Some(reference_segment::ReferenceType::StructField(Box::new(
+                        StructField {
+                            field: index as i32,
+                            child: None,
+                        },
+                    ))),
+                })),
+                root_type: None,
+                expr_name: name.clone(),
+            }))
+        }
+        DfExpression::Literal(value) => RexType::Literal(literal_to_substrait(value)?),
+        DfExpression::ScalarFunction { op, args } => {
+            let anchor = registry.anchor(op);
+            let arguments = args
+                .iter()
+                .map(|arg| {
+                    Ok(FunctionArgument {
+                        arg_type: Some(ArgType::Value(expression_to_substrait(
+                            arg, schema, registry,
+                        )?)),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            RexType::ScalarFunction(ScalarFunction {
+                function_reference: anchor,
+                arguments,
+                ..Default::default()
+            })
+        }
+        DfExpression::Alias(_, inner) => return expression_to_substrait(inner, schema, registry),
+        other => {
+            return Err(ErrorCode::UnImplement(format!(
+                "Substrait producer does not support expression '{:?}' yet",
+                other
+            )));
+        }
+    };
+
+    Ok(Expression {
+        rex_type: Some(rex_type),
+    })
+}
+
+fn literal_to_substrait(value: &common_datavalues::DataValue) -> Result<Literal> {
+    use common_datavalues::DataValue;
+
+    let literal_type = match value {
+        DataValue::Boolean(Some(v)) => LiteralType::Boolean(*v),
+        DataValue::Int64(Some(v)) => LiteralType::I64(*v),
+        DataValue::UInt64(Some(v)) => LiteralType::I64(*v as i64),
+        DataValue::Float64(Some(v)) => LiteralType::Fp64(*v),
+        DataValue::String(Some(v)) => LiteralType::String(String::from_utf8_lossy(v).into_owned()),
+        DataValue::Null => {
+            return Err(ErrorCode::UnImplement(
+                "Substrait producer does not yet encode typed NULL literals",
+            ))
+        }
+        other => {
+            return Err(ErrorCode::UnImplement(format!(
+                "Substrait producer does not support literal '{:?}' yet",
+                other
+            )));
+        }
+    };
+
+    Ok(Literal {
+        nullable: true,
+        type_variation_reference: 0,
+        literal_type: Some(literal_type),
+    })
+}
+
+fn output_names(plan: &PlanNode) -> Vec<String> {
+    plan.schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use common_datavalues::DataField;
+    use common_datavalues::DataSchemaRefExt;
+    use common_datavalues::DataType;
+    use common_datavalues::DataValue;
+
+    use super::*;
+
+    fn two_column_schema() -> DataSchemaRef {
+        DataSchemaRefExt::create(vec![
+            DataField::new("a", DataType::Int64, false),
+            DataField::new("b", DataType::Int64, false),
+        ])
+    }
+
+    #[test]
+    fn test_column_reference_resolves_real_field_index() {
+        let schema = two_column_schema();
+        let mut registry = FunctionRegistry::new();
+
+        // Regression test for producing `field: 0` regardless of which
+        // column was actually referenced: "b" is field 1, not field 0.
+        let expr = expression_to_substrait(
+            &DfExpression::Column("b".to_string()),
+            &schema,
+            &mut registry,
+        )
+        .unwrap();
+
+        match expr.rex_type {
+            Some(RexType::Selection(selection)) => match selection.reference_type {
+                Some(FieldReferenceType::DirectReference(segment)) => {
+                    match segment.reference_type {
+                        Some(reference_segment::ReferenceType::StructField(field)) => {
+                            assert_eq!(field.field, 1);
+                        }
+                        other => panic!("unexpected reference type: {:?}", other),
+                    }
+                }
+                other => panic!("unexpected field reference type: {:?}", other),
+            },
+            other => panic!("unexpected rex type: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unknown_column_is_rejected() {
+        let schema = two_column_schema();
+        let mut registry = FunctionRegistry::new();
+
+        let err = expression_to_substrait(
+            &DfExpression::Column("does_not_exist".to_string()),
+            &schema,
+            &mut registry,
+        )
+        .unwrap_err();
+        assert!(err.message().contains("could not resolve column"));
+    }
+
+    #[test]
+    fn test_literal_to_substrait_encodes_int64_as_i64() {
+        let literal = literal_to_substrait(&DataValue::Int64(Some(42))).unwrap();
+        assert_eq!(literal.literal_type, Some(LiteralType::I64(42)));
+    }
+}
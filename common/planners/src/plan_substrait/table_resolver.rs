@@ -0,0 +1,32 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use common_exception::Result;
+
+use crate::PlanNode;
+
+/// Looks up the table a Substrait `ReadRel.NamedTable` refers to and
+/// builds the `PlanNode::ReadSource` plan for it.
+///
+/// `common_planners` has no notion of a query session or catalog — that
+/// lives in the `query` crate, which already depends on `common_planners`
+/// — so [`super::from_substrait`] takes this trait instead of a concrete
+/// context type, keeping the dependency pointed one way.
+pub trait SubstraitTableResolver {
+    /// The database a bare (single-part) table name should resolve
+    /// against, e.g. the session's current database.
+    fn current_database(&self) -> String;
+
+    fn read_table_plan(&self, db: &str, table: &str) -> Result<PlanNode>;
+}
@@ -0,0 +1,40 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use common_exception::Result;
+use common_planners::PlanNode;
+use common_planners::SubstraitTableResolver;
+use substrait::proto::Plan;
+
+use crate::sessions::DatabendQueryContextRef;
+
+/// `common_planners` can't depend on a query session or catalog, so it
+/// takes a [`SubstraitTableResolver`] instead; this is the only place in
+/// the query crate that bridges the two, letting everything above here
+/// keep working against a plain `DatabendQueryContextRef`.
+impl SubstraitTableResolver for DatabendQueryContextRef {
+    fn current_database(&self) -> String {
+        self.get_current_database()
+    }
+
+    fn read_table_plan(&self, db: &str, table: &str) -> Result<PlanNode> {
+        self.get_table(db, table)?.read_plan(self.clone(), None)
+    }
+}
+
+/// Converts a Substrait `Plan` into a Databend `PlanNode`, resolving any
+/// table reads against `ctx`.
+pub fn from_substrait(plan: &Plan, ctx: &DatabendQueryContextRef) -> Result<PlanNode> {
+    common_planners::from_substrait(plan, ctx)
+}
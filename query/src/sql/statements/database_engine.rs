@@ -0,0 +1,169 @@
+// Copyright 2022 Datafuse Labs.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use common_exception::ErrorCode;
+use common_exception::Result;
+use lazy_static::lazy_static;
+
+/// Describes a database engine that `CREATE DATABASE ... ENGINE = name`
+/// may reference, and which `WITH` options it accepts.
+pub struct DatabaseEngineDescriptor {
+    pub name: String,
+    pub required_options: HashSet<String>,
+    pub allowed_options: HashSet<String>,
+}
+
+impl DatabaseEngineDescriptor {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            required_options: HashSet::new(),
+            allowed_options: HashSet::new(),
+        }
+    }
+
+    pub fn with_allowed_option(mut self, option: impl Into<String>) -> Self {
+        self.allowed_options.insert(option.into());
+        self
+    }
+
+    pub fn with_required_option(mut self, option: impl Into<String>) -> Self {
+        let option = option.into();
+        self.allowed_options.insert(option.clone());
+        self.required_options.insert(option);
+        self
+    }
+}
+
+lazy_static! {
+    static ref DATABASE_ENGINES: Mutex<HashMap<String, DatabaseEngineDescriptor>> = {
+        let mut registry = HashMap::new();
+        register(&mut registry, DatabaseEngineDescriptor::new("DEFAULT"));
+        Mutex::new(registry)
+    };
+}
+
+fn register(
+    registry: &mut HashMap<String, DatabaseEngineDescriptor>,
+    descriptor: DatabaseEngineDescriptor,
+) {
+    registry.insert(descriptor.name.to_uppercase(), descriptor);
+}
+
+/// Lets a storage engine declare the options it accepts on
+/// `CREATE DATABASE ... ENGINE = name [WITH ...]`, so the analyzer can
+/// validate them up front instead of failing later when the engine is
+/// actually opened.
+pub fn register_database_engine(descriptor: DatabaseEngineDescriptor) {
+    register(&mut DATABASE_ENGINES.lock().unwrap(), descriptor);
+}
+
+/// Resolves `engine` against the registry and checks `options` against
+/// its required/allowed sets, returning the normalized (upper-cased)
+/// engine name on success.
+///
+/// An engine that hasn't called [`register_database_engine`] yet isn't
+/// necessarily wrong — it may simply not have been wired into this
+/// registry — so `CREATE DATABASE ... ENGINE = x` with no `WITH` options
+/// passes an unregistered `x` through unchanged rather than breaking
+/// existing databases on it. Options can only be checked against a
+/// descriptor, so an unregistered engine that does specify options is
+/// still rejected: there is nothing to validate them against.
+pub fn validate_database_engine(engine: &str, options: &HashMap<String, String>) -> Result<String> {
+    let normalized = engine.to_uppercase();
+    let registry = DATABASE_ENGINES.lock().unwrap();
+    let descriptor = match registry.get(&normalized) {
+        Some(descriptor) => descriptor,
+        None if options.is_empty() => return Ok(normalized),
+        None => {
+            return Err(ErrorCode::BadArguments(format!(
+                "Unknown database engine '{}', available engines: {:?}",
+                engine,
+                registry.keys().collect::<Vec<_>>()
+            )));
+        }
+    };
+
+    for required in &descriptor.required_options {
+        if !options.contains_key(required) {
+            return Err(ErrorCode::BadArguments(format!(
+                "Database engine '{}' requires option '{}'",
+                descriptor.name, required
+            )));
+        }
+    }
+
+    for option in options.keys() {
+        if !descriptor.allowed_options.contains(option) {
+            return Err(ErrorCode::BadArguments(format!(
+                "Database engine '{}' does not accept option '{}', allowed options: {:?}",
+                descriptor.name, option, descriptor.allowed_options
+            )));
+        }
+    }
+
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_engine_rejects_unknown_option() {
+        let mut options = HashMap::new();
+        options.insert("foo".to_string(), "bar".to_string());
+
+        let err = validate_database_engine("default", &options).unwrap_err();
+        assert!(err.message().contains("does not accept option"));
+    }
+
+    #[test]
+    fn test_unregistered_engine_without_options_passes_through() {
+        // An engine that hasn't registered itself yet shouldn't break
+        // existing `CREATE DATABASE ... ENGINE = x` statements that don't
+        // rely on option validation.
+        let engine = validate_database_engine("not_yet_registered", &HashMap::new()).unwrap();
+        assert_eq!(engine, "NOT_YET_REGISTERED");
+    }
+
+    #[test]
+    fn test_unregistered_engine_with_options_is_rejected() {
+        let mut options = HashMap::new();
+        options.insert("url".to_string(), "http://example.com".to_string());
+
+        let err = validate_database_engine("not_a_real_engine", &options).unwrap_err();
+        assert!(err.message().contains("Unknown database engine"));
+    }
+
+    #[test]
+    fn test_custom_engine_required_option() {
+        register_database_engine(
+            DatabaseEngineDescriptor::new("test_required_option_engine")
+                .with_required_option("url"),
+        );
+
+        let err =
+            validate_database_engine("test_required_option_engine", &HashMap::new()).unwrap_err();
+        assert!(err.message().contains("requires option"));
+
+        let mut options = HashMap::new();
+        options.insert("url".to_string(), "http://example.com".to_string());
+        assert!(validate_database_engine("test_required_option_engine", &options).is_ok());
+    }
+}
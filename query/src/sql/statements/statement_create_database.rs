@@ -22,9 +22,12 @@ use sqlparser::ast::ObjectName;
 use sqlparser::ast::SqlOption;
 
 use crate::sessions::DatabendQueryContextRef;
+use crate::sql::statements::database_engine::validate_database_engine;
 use crate::sql::statements::AnalyzableStatement;
 use crate::sql::statements::AnalyzedResult;
 
+const DEFAULT_DATABASE_ENGINE: &str = "DEFAULT";
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct DfCreateDatabase {
     pub if_not_exists: bool,
@@ -36,12 +39,18 @@ pub struct DfCreateDatabase {
 impl AnalyzableStatement for DfCreateDatabase {
     async fn analyze(&self, _ctx: DatabendQueryContextRef) -> Result<AnalyzedResult> {
         let db = self.database_name()?;
-        let options = self.database_options();
+        let mut options = self.database_options();
         let if_not_exists = self.if_not_exists;
 
+        let engine = options
+            .remove("engine")
+            .unwrap_or_else(|| DEFAULT_DATABASE_ENGINE.to_string());
+        let engine = validate_database_engine(&engine, &options)?;
+
         Ok(AnalyzedResult::SimpleQuery(PlanNode::CreateDatabase(
             CreateDatabasePlan {
                 db,
+                engine,
                 options,
                 if_not_exists,
             },